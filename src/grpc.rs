@@ -0,0 +1,69 @@
+use crate::store::{PointsStore, SharedStore};
+use crate::{calculate_points, Item as HttpItem, Receipt as HttpReceipt};
+use tonic::{Request, Response, Status};
+
+pub(crate) mod proto {
+    tonic::include_proto!("receipt");
+}
+
+use proto::receipt_processor_server::{ReceiptProcessor, ReceiptProcessorServer};
+use proto::{PointsRequest, PointsResponse, ProcessResponse, Receipt};
+
+impl From<Receipt> for HttpReceipt {
+    fn from(receipt: Receipt) -> Self {
+        HttpReceipt {
+            retailer: receipt.retailer,
+            date: receipt.purchase_date,
+            time: receipt.purchase_time,
+            items: receipt
+                .items
+                .into_iter()
+                .map(|item| HttpItem {
+                    desc: item.short_description,
+                    price: item.price,
+                })
+                .collect(),
+            total: receipt.total,
+        }
+    }
+}
+
+/// Thin tonic adapter: no scoring or storage logic lives here, it just
+/// forwards to the same store and scoring function the REST handlers use.
+pub(crate) struct GrpcReceiptProcessor {
+    store: SharedStore,
+}
+
+impl GrpcReceiptProcessor {
+    pub(crate) fn into_server(store: SharedStore) -> ReceiptProcessorServer<Self> {
+        ReceiptProcessorServer::new(Self { store })
+    }
+}
+
+#[tonic::async_trait]
+impl ReceiptProcessor for GrpcReceiptProcessor {
+    async fn process_receipt(
+        &self,
+        request: Request<Receipt>,
+    ) -> Result<Response<ProcessResponse>, Status> {
+        let receipt: HttpReceipt = request.into_inner().into();
+        let points = calculate_points(&receipt)
+            .map_err(|err| Status::invalid_argument(format!("{}: {}", err.field, err.message)))?;
+
+        let id = uuid::Uuid::new_v4().to_string();
+        self.store.insert(id.clone(), points);
+
+        Ok(Response::new(ProcessResponse { id }))
+    }
+
+    async fn get_points(
+        &self,
+        request: Request<PointsRequest>,
+    ) -> Result<Response<PointsResponse>, Status> {
+        let id = request.into_inner().id;
+        match self.store.get(&id) {
+            Some(points) => Ok(Response::new(PointsResponse { points })),
+            None => Err(Status::not_found(format!("no points for id {id}"))),
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use crate::store::{PointsStore, SharedStore};
+use crate::{calculate_points, Receipt};
+use rdkafka::client::ClientContext;
+use rdkafka::config::ClientConfig;
+use rdkafka::consumer::{Consumer, ConsumerContext, StreamConsumer};
+use rdkafka::message::Message;
+use rdkafka::producer::{FutureProducer, FutureRecord};
+
+/// Env vars for streaming ingestion. Setting `KAFKA_BROKERS` turns it on; when
+/// it is unset, no consumer is started and receipts arrive only through the
+/// REST and gRPC servers.
+const BROKERS_ENV: &str = "KAFKA_BROKERS";
+const TOPIC_ENV: &str = "KAFKA_RECEIPTS_TOPIC";
+const RESULTS_TOPIC_ENV: &str = "KAFKA_RESULTS_TOPIC";
+
+struct LoggingContext;
+impl ClientContext for LoggingContext {}
+impl ConsumerContext for LoggingContext {}
+
+/// Reads Kafka config from the environment, returning `None` when streaming
+/// ingestion hasn't been configured.
+pub(crate) fn config_from_env() -> Option<(String, String, Option<String>)> {
+    let brokers = std::env::var(BROKERS_ENV).ok()?;
+    let topic = std::env::var(TOPIC_ENV).unwrap_or_else(|_| "receipts".to_string());
+    let results_topic = std::env::var(RESULTS_TOPIC_ENV).ok();
+    Some((brokers, topic, results_topic))
+}
+
+/// Subscribes to `topic` and scores each incoming receipt with the same
+/// [`calculate_points`] used by the HTTP handler, storing the result in
+/// `store` under a freshly generated id.
+pub(crate) async fn run(
+    brokers: String,
+    topic: String,
+    results_topic: Option<String>,
+    store: SharedStore,
+) {
+    let consumer: StreamConsumer<LoggingContext> = ClientConfig::new()
+        .set("bootstrap.servers", &brokers)
+        .set("group.id", "receipt-processor")
+        .set("enable.auto.commit", "true")
+        .create_with_context(LoggingContext)
+        .expect("failed to create Kafka consumer");
+
+    consumer
+        .subscribe(&[topic.as_str()])
+        .expect("failed to subscribe to Kafka topic");
+
+    let producer: Option<FutureProducer> = results_topic.as_ref().map(|_| {
+        ClientConfig::new()
+            .set("bootstrap.servers", &brokers)
+            .create()
+            .expect("failed to create Kafka producer")
+    });
+
+    loop {
+        match consumer.recv().await {
+            Ok(message) => {
+                let Some(payload) = message.payload() else {
+                    continue;
+                };
+                let receipt: Receipt = match serde_json::from_slice(payload) {
+                    Ok(receipt) => receipt,
+                    Err(err) => {
+                        eprintln!("dropping unparseable Kafka receipt: {err}");
+                        continue;
+                    }
+                };
+
+                let points = match calculate_points(&receipt) {
+                    Ok(points) => points,
+                    Err(err) => {
+                        eprintln!("dropping invalid Kafka receipt: {} ({})", err.field, err.message);
+                        continue;
+                    }
+                };
+
+                let id = uuid::Uuid::new_v4().to_string();
+                store.insert(id.clone(), points);
+
+                if let (Some(producer), Some(results_topic)) = (&producer, &results_topic) {
+                    let record = FutureRecord::to(results_topic).key(&id).payload(&id);
+                    let _ = producer.send(record, std::time::Duration::from_secs(0)).await;
+                }
+            }
+            Err(err) => eprintln!("Kafka consumer error: {err}"),
+        }
+    }
+}
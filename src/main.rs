@@ -1,36 +1,42 @@
+use axum::extract::State;
+use axum::http::StatusCode;
 use axum::routing::{get, post};
 use axum::{Json, Router};
-use lazy_static::lazy_static;
+use chrono::{Datelike, NaiveDate, NaiveTime};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::sync::{Arc, RwLock};
-use std::usize;
+use std::time::Duration;
 
-type IdMap = Arc<RwLock<HashMap<String, String>>>;
-lazy_static! {
-    static ref ID_MAP: IdMap = {
-        let mut map = HashMap::new();
-        let rw_lock = RwLock::new(map);
-        Arc::new(rw_lock)
-    };
+mod grpc;
+mod kafka;
+mod store;
+
+use store::{PointsStore, SharedStore};
+
+/// How often the in-memory map is flushed to the on-disk snapshot.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(30);
+
+fn flush_store(store: &SharedStore) {
+    if let Err(err) = store::save(&store::store_path(), &store.snapshot()) {
+        eprintln!("failed to flush points store: {err}");
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-struct Receipt {
-    retailer: String,
+pub(crate) struct Receipt {
+    pub(crate) retailer: String,
     #[serde(rename = "purchaseDate")]
-    date: String,
+    pub(crate) date: String,
     #[serde(rename = "purchaseTime")]
-    time: String,
-    items: Vec<Item>,
-    total: String,
+    pub(crate) time: String,
+    pub(crate) items: Vec<Item>,
+    pub(crate) total: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-struct Item {
+pub(crate) struct Item {
     #[serde(rename = "shortDescription")]
-    desc: String,
-    price: String,
+    pub(crate) desc: String,
+    pub(crate) price: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -40,111 +46,192 @@ struct ProcessResponse {
 
 #[derive(Debug, Serialize, Deserialize)]
 struct PointsResponse {
-    points: String,
+    points: u64,
 }
 
-async fn process_receipt(receipt: axum::extract::Json<Receipt>) -> Json<ProcessResponse> {
-    let id = uuid::Uuid::new_v4().to_string();
+/// A machine-readable 400 response naming the offending field.
+#[derive(Debug, Serialize)]
+pub(crate) struct ErrorBody {
+    pub(crate) field: String,
+    pub(crate) message: String,
+}
+
+fn bad_request(field: &str, message: impl Into<String>) -> ErrorBody {
+    ErrorBody {
+        field: field.to_string(),
+        message: message.into(),
+    }
+}
+
+/// Parses a currency amount that must have exactly two fractional digits,
+/// e.g. `"6.49"`, matching the precision receipts are expected to report.
+fn parse_money(field: &str, value: &str) -> Result<f64, ErrorBody> {
+    match value.split_once('.') {
+        Some((_, fraction)) if fraction.len() == 2 => value
+            .parse::<f64>()
+            .map_err(|_| bad_request(field, format!("`{value}` is not a valid amount"))),
+        _ => Err(bad_request(
+            field,
+            format!("`{value}` must have exactly two fractional digits"),
+        )),
+    }
+}
 
-    // calculate points
-    let alphanum: usize = receipt
+/// Scores a receipt against the published rules. The HTTP handler and the
+/// Kafka consumer both call this directly, so a receipt is worth the same
+/// number of points no matter which path it came in on.
+pub(crate) fn calculate_points(receipt: &Receipt) -> Result<u64, ErrorBody> {
+    let date = NaiveDate::parse_from_str(&receipt.date, "%Y-%m-%d")
+        .map_err(|_| bad_request("purchaseDate", format!("`{}` is not a valid date", receipt.date)))?;
+    let time = NaiveTime::parse_from_str(&receipt.time, "%H:%M")
+        .map_err(|_| bad_request("purchaseTime", format!("`{}` is not a valid time", receipt.time)))?;
+    let total = parse_money("total", &receipt.total)?;
+
+    let alphanum: u64 = receipt
         .retailer
         .chars()
         .filter(|c| c.is_alphanumeric())
-        .count();
-
-    let (round, quarter): (usize, usize) = if let Ok(amount) = receipt.total.parse::<f64>() {
-        let r: usize = if amount - amount.trunc() == 0.0 {
-            50
-        } else {
-            0
-        };
-
-        let q: usize = if (amount * 100.0) % 25.0 == 0.0 {
-            25
-        } else {
-            0
-        };
-        (r, q)
-    } else {
-        (0, 0)
-    };
-
-    let mut item_points = 5 * (receipt.items.len() / 2);
-    for item in receipt.items.iter() {
-        if let Ok(price) = item.price.parse::<f64>() {
-            if item.desc.trim().len() % 3 == 0 {
-                let price = price * 0.2;
-                let price: usize = price.ceil() as usize;
-                item_points += price
-            }
+        .count() as u64;
+
+    let round: u64 = if total - total.trunc() == 0.0 { 50 } else { 0 };
+    let quarter: u64 = if (total * 100.0) % 25.0 == 0.0 { 25 } else { 0 };
+
+    let mut item_points = 5 * (receipt.items.len() as u64 / 2);
+    for (i, item) in receipt.items.iter().enumerate() {
+        let price = parse_money(&format!("items[{i}].price"), &item.price)?;
+        if item.desc.trim().len() % 3 == 0 {
+            let price = (price * 0.2).ceil() as u64;
+            item_points += price;
         }
     }
 
-    let odd: usize = if odd_date(&receipt.date) { 6 } else { 0 };
-    let time: usize = if time_check(&receipt.time) { 10 } else { 0 };
+    let odd: u64 = if odd_date(date) { 6 } else { 0 };
+    let time_bonus: u64 = if time_check(time) { 10 } else { 0 };
 
-    let points = alphanum + round + quarter + item_points + odd + time;
+    Ok(alphanum + round + quarter + item_points + odd + time_bonus)
+}
 
-    if let Ok(mut id_map) = ID_MAP.write() {
-        id_map.insert(id.clone(), format!("{}", points));
-    }
+async fn process_receipt(
+    State(store): State<SharedStore>,
+    receipt: axum::extract::Json<Receipt>,
+) -> Result<Json<ProcessResponse>, (StatusCode, Json<ErrorBody>)> {
+    let points = calculate_points(&receipt).map_err(|err| (StatusCode::BAD_REQUEST, Json(err)))?;
 
-    let response = ProcessResponse { id };
-    Json(response)
+    let id = uuid::Uuid::new_v4().to_string();
+    store.insert(id.clone(), points);
+
+    Ok(Json(ProcessResponse { id }))
 }
 
-fn odd_date(date_str: &str) -> bool {
-    if let Some(day_str) = date_str.split('-').nth(2) {
-        if let Ok(day) = day_str.parse::<usize>() {
-            return day % 2 != 0;
-        }
-    }
-    false
-}
-
-fn time_check(time_str: &str) -> bool {
-    if let Some((hour, minute)) = time_str
-        .split(':')
-        .next()
-        .and_then(|hour| time_str.split(':').nth(1).map(|minute| (hour, minute)))
-    {
-        if let (Ok(hour), Ok(minute)) = (hour.parse::<u32>(), minute.parse::<u32>()) {
-            return (hour == 14 && minute >= 0)
-                || (hour == 15 && minute == 0)
-                || (hour == 16 && minute == 0);
-        }
-    }
-    false
+fn odd_date(date: NaiveDate) -> bool {
+    date.day() % 2 != 0
 }
 
-async fn get_receipt_points(id: axum::extract::Path<String>) -> Json<PointsResponse> {
+fn time_check(time: NaiveTime) -> bool {
+    let start = NaiveTime::from_hms_opt(14, 0, 0).unwrap();
+    let end = NaiveTime::from_hms_opt(16, 0, 0).unwrap();
+    time > start && time < end
+}
 
-    let id = id.to_string();
-    if let Ok(mut id_map) = ID_MAP.write() {
-        let default = "Unknown".to_string();
-        let points = id_map.get(&id).unwrap_or(&default);
-        let response = PointsResponse{
-            points: points.to_string()
-        };
-        return Json(response);
+async fn get_receipt_points(
+    State(store): State<SharedStore>,
+    id: axum::extract::Path<String>,
+) -> Result<Json<PointsResponse>, StatusCode> {
+    match store.get(&id) {
+        Some(points) => Ok(Json(PointsResponse { points })),
+        None => Err(StatusCode::NOT_FOUND),
     }
-    let response = PointsResponse{
-        points: format!("unknown")
-    };
-    return Json(response)
 }
 
 #[tokio::main]
 async fn main() {
+    let store = SharedStore::new(store::load(&store::store_path()));
+
+    tokio::spawn({
+        let store = store.clone();
+        async move {
+            let mut interval = tokio::time::interval(FLUSH_INTERVAL);
+            loop {
+                interval.tick().await;
+                flush_store(&store);
+            }
+        }
+    });
+
+    if let Some((brokers, topic, results_topic)) = kafka::config_from_env() {
+        let store = store.clone();
+        tokio::spawn(async move {
+            kafka::run(brokers, topic, results_topic, store).await;
+        });
+    }
+
+    tokio::spawn({
+        let store = store.clone();
+        async move {
+            let addr = "0.0.0.0:50051".parse().unwrap();
+            println!("gRPC server running on {addr}");
+            tonic::transport::Server::builder()
+                .add_service(grpc::GrpcReceiptProcessor::into_server(store))
+                .serve(addr)
+                .await
+                .unwrap();
+        }
+    });
+
     let app = Router::new()
         .route("/receipts/process", post(process_receipt))
-        .route("/receipts/:id/points", get(get_receipt_points));
+        .route("/receipts/:id/points", get(get_receipt_points))
+        .with_state(store.clone());
 
     let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
     println!(
         "Server running on http://{}",
         listener.local_addr().unwrap()
     );
-    axum::serve(listener, app).await.unwrap();
+    axum::serve(listener, app)
+        .with_graceful_shutdown(shutdown_signal())
+        .await
+        .unwrap();
+
+    flush_store(&store);
+}
+
+/// Waits for Ctrl+C so the points store gets one last flush before exit.
+async fn shutdown_signal() {
+    tokio::signal::ctrl_c()
+        .await
+        .expect("failed to install Ctrl+C handler");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_money_accepts_two_fractional_digits() {
+        assert_eq!(parse_money("total", "6.49").unwrap(), 6.49);
+        assert_eq!(parse_money("total", "10.00").unwrap(), 10.0);
+    }
+
+    #[test]
+    fn parse_money_rejects_wrong_precision() {
+        assert!(parse_money("total", "6.4").is_err());
+        assert!(parse_money("total", "6.499").is_err());
+        assert!(parse_money("total", "6").is_err());
+    }
+
+    #[test]
+    fn odd_date_checks_the_day_of_month() {
+        assert!(odd_date(NaiveDate::from_ymd_opt(2022, 1, 1).unwrap()));
+        assert!(!odd_date(NaiveDate::from_ymd_opt(2022, 1, 2).unwrap()));
+    }
+
+    #[test]
+    fn time_check_excludes_the_boundary_and_includes_the_middle() {
+        assert!(!time_check(NaiveTime::from_hms_opt(14, 0, 0).unwrap()));
+        assert!(time_check(NaiveTime::from_hms_opt(14, 1, 0).unwrap()));
+        assert!(time_check(NaiveTime::from_hms_opt(15, 59, 0).unwrap()));
+        assert!(!time_check(NaiveTime::from_hms_opt(16, 0, 0).unwrap()));
+        assert!(!time_check(NaiveTime::from_hms_opt(13, 59, 0).unwrap()));
+    }
 }
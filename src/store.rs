@@ -0,0 +1,185 @@
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+const DEFAULT_STORE_PATH: &str = "points_store.json.gz";
+
+/// Bumped whenever the on-disk snapshot's shape changes, so an old snapshot
+/// is dropped instead of silently misread as the new shape.
+const SNAPSHOT_VERSION: u32 = 2;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Snapshot {
+    version: u32,
+    points: HashMap<String, u64>,
+}
+
+/// Where the points snapshot lives, overridable via `POINTS_STORE_PATH`.
+pub(crate) fn store_path() -> PathBuf {
+    std::env::var("POINTS_STORE_PATH")
+        .unwrap_or_else(|_| DEFAULT_STORE_PATH.to_string())
+        .into()
+}
+
+/// Load a previously persisted id->points map, or an empty one if the
+/// snapshot doesn't exist, fails to parse, or was written by an incompatible
+/// version.
+pub(crate) fn load(path: &Path) -> HashMap<String, u64> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(_) => return HashMap::new(),
+    };
+
+    let mut contents = String::new();
+    if let Err(err) = GzDecoder::new(file).read_to_string(&mut contents) {
+        eprintln!("failed to decompress points snapshot at {}: {err}", path.display());
+        return HashMap::new();
+    }
+
+    let snapshot: Snapshot = match serde_json::from_str(&contents) {
+        Ok(snapshot) => snapshot,
+        Err(err) => {
+            eprintln!("failed to parse points snapshot at {}: {err}", path.display());
+            return HashMap::new();
+        }
+    };
+
+    if snapshot.version != SNAPSHOT_VERSION {
+        eprintln!(
+            "ignoring points snapshot at {} written by incompatible version {} (expected {SNAPSHOT_VERSION})",
+            path.display(),
+            snapshot.version
+        );
+        return HashMap::new();
+    }
+
+    snapshot.points
+}
+
+/// Gzip-compress and write the id->points map to `path`.
+pub(crate) fn save(path: &Path, map: &HashMap<String, u64>) -> std::io::Result<()> {
+    let snapshot = Snapshot {
+        version: SNAPSHOT_VERSION,
+        points: map.clone(),
+    };
+    let json = serde_json::to_string(&snapshot)?;
+    let file = File::create(path)?;
+    let mut encoder = GzEncoder::new(file, Compression::default());
+    encoder.write_all(json.as_bytes())?;
+    encoder.finish()?;
+    Ok(())
+}
+
+/// Read/write access to the id->points map, backed by an `RwLock` so many
+/// concurrent lookups can proceed while a write is exclusive.
+pub(crate) trait PointsStore: Send + Sync {
+    fn insert(&self, id: String, points: u64);
+    fn get(&self, id: &str) -> Option<u64>;
+}
+
+#[derive(Clone)]
+pub(crate) struct SharedStore {
+    map: Arc<RwLock<HashMap<String, u64>>>,
+}
+
+impl SharedStore {
+    pub(crate) fn new(initial: HashMap<String, u64>) -> Self {
+        Self {
+            map: Arc::new(RwLock::new(initial)),
+        }
+    }
+
+    /// A point-in-time copy of the map, for flushing to disk.
+    pub(crate) fn snapshot(&self) -> HashMap<String, u64> {
+        self.map.read().map(|map| map.clone()).unwrap_or_default()
+    }
+}
+
+impl PointsStore for SharedStore {
+    fn insert(&self, id: String, points: u64) {
+        if let Ok(mut map) = self.map.write() {
+            map.insert(id, points);
+        }
+    }
+
+    fn get(&self, id: &str) -> Option<u64> {
+        self.map.read().ok()?.get(id).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("receipt-processor-{}-{name}.gz", std::process::id()))
+    }
+
+    #[test]
+    fn save_then_load_round_trips() {
+        let path = scratch_path("round-trip");
+        let mut map = HashMap::new();
+        map.insert("abc-123".to_string(), 42u64);
+
+        save(&path, &map).unwrap();
+        let loaded = load(&path);
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(loaded, map);
+    }
+
+    #[test]
+    fn load_missing_file_returns_empty_map() {
+        let path = scratch_path("missing");
+        std::fs::remove_file(&path).ok();
+
+        assert!(load(&path).is_empty());
+    }
+
+    #[test]
+    fn shared_store_insert_then_get_round_trips() {
+        let store = SharedStore::new(HashMap::new());
+        store.insert("abc-123".to_string(), 42);
+        assert_eq!(store.get("abc-123"), Some(42));
+        assert_eq!(store.get("missing"), None);
+    }
+
+    #[test]
+    fn load_ignores_a_snapshot_from_an_incompatible_version() {
+        let path = scratch_path("bad-version");
+        let mut map = HashMap::new();
+        map.insert("abc-123".to_string(), 42u64);
+
+        let snapshot = Snapshot {
+            version: SNAPSHOT_VERSION + 1,
+            points: map,
+        };
+        let json = serde_json::to_string(&snapshot).unwrap();
+        let file = File::create(&path).unwrap();
+        let mut encoder = GzEncoder::new(file, Compression::default());
+        encoder.write_all(json.as_bytes()).unwrap();
+        encoder.finish().unwrap();
+
+        let loaded = load(&path);
+        std::fs::remove_file(&path).ok();
+
+        assert!(loaded.is_empty());
+    }
+
+    #[test]
+    fn load_ignores_a_corrupt_file() {
+        let path = scratch_path("corrupt");
+        std::fs::write(&path, b"not a gzip file").unwrap();
+
+        let loaded = load(&path);
+        std::fs::remove_file(&path).ok();
+
+        assert!(loaded.is_empty());
+    }
+}